@@ -4,18 +4,16 @@ use crate::parser::{Cursor, Parse, Parser, Peek, Result};
 /// The value types for a wasm module.
 #[allow(missing_docs)]
 #[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
-pub enum ValType {
+pub enum ValType<'a> {
     I32,
     I64,
     F32,
     F64,
-    Anyref,
-    Funcref,
     V128,
-    Nullref,
+    Ref(RefType<'a>),
 }
 
-impl<'a> Parse<'a> for ValType {
+impl<'a> Parse<'a> for ValType<'a> {
     fn parse(parser: Parser<'a>) -> Result<Self> {
         let mut l = parser.lookahead1();
         if l.peek::<kw::i32>() {
@@ -30,142 +28,337 @@ impl<'a> Parse<'a> for ValType {
         } else if l.peek::<kw::f64>() {
             parser.parse::<kw::f64>()?;
             Ok(ValType::F64)
-        } else if l.peek::<kw::anyref>() {
-            parser.parse::<kw::anyref>()?;
-            Ok(ValType::Anyref)
-        } else if l.peek::<kw::funcref>() {
-            parser.parse::<kw::funcref>()?;
-            Ok(ValType::Funcref)
-        } else if l.peek::<kw::anyfunc>() {
-            parser.parse::<kw::anyfunc>()?;
-            Ok(ValType::Funcref)
-        } else if l.peek::<kw::nullref>() {
-            parser.parse::<kw::nullref>()?;
-            Ok(ValType::Nullref)
         } else if l.peek::<kw::v128>() {
             parser.parse::<kw::v128>()?;
             Ok(ValType::V128)
+        } else if RefType::peek(parser.cursor()) {
+            Ok(ValType::Ref(parser.parse()?))
         } else {
             Err(l.error())
         }
     }
 }
 
-/// Type for a `global` in a wasm module
-#[derive(Copy, Clone, Debug)]
-pub struct GlobalType {
-    /// The element type of this `global`
-    pub ty: ValType,
-    /// Whether or not the global is mutable or not.
-    pub mutable: bool,
+/// The heap types that a reference type can point to.
+///
+/// This is part of the reference-types/function-references/GC proposals and
+/// describes the "kind" of value a `RefType` can refer to, independent of
+/// nullability.
+#[allow(missing_docs)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum HeapType<'a> {
+    /// An untyped function reference: the common supertype of all function
+    /// references.
+    Func,
+    /// An external reference: the common supertype of all external
+    /// references.
+    Extern,
+    /// The common supertype of all (internal) references.
+    Any,
+    /// The common supertype of all referenceable structs and arrays.
+    Eq,
+    /// An unboxed 31-bit integer.
+    I31,
+    /// The common subtype (bottom type) of all internal reference types.
+    None,
+    /// The common subtype (bottom type) of all function references.
+    NoFunc,
+    /// The common subtype (bottom type) of all external references.
+    NoExtern,
+    /// An exception reference, part of the exception-handling proposal.
+    Exn,
+    /// The common subtype (bottom type) of all exception references.
+    NoExn,
+    /// A reference to a concrete type defined by index, e.g. a `struct`,
+    /// `array`, or `func` type.
+    Concrete(ast::Index<'a>),
 }
 
-impl<'a> Parse<'a> for GlobalType {
+impl<'a> Parse<'a> for HeapType<'a> {
     fn parse(parser: Parser<'a>) -> Result<Self> {
-        if parser.peek2::<kw::r#mut>() {
-            parser.parens(|p| {
-                p.parse::<kw::r#mut>()?;
-                Ok(GlobalType {
-                    ty: parser.parse()?,
-                    mutable: true,
-                })
-            })
+        let mut l = parser.lookahead1();
+        if l.peek::<kw::func>() {
+            parser.parse::<kw::func>()?;
+            Ok(HeapType::Func)
+        } else if l.peek::<kw::extern_>() {
+            parser.parse::<kw::extern_>()?;
+            Ok(HeapType::Extern)
+        } else if l.peek::<kw::any>() {
+            parser.parse::<kw::any>()?;
+            Ok(HeapType::Any)
+        } else if l.peek::<kw::eq>() {
+            parser.parse::<kw::eq>()?;
+            Ok(HeapType::Eq)
+        } else if l.peek::<kw::i31>() {
+            parser.parse::<kw::i31>()?;
+            Ok(HeapType::I31)
+        } else if l.peek::<kw::none>() {
+            parser.parse::<kw::none>()?;
+            Ok(HeapType::None)
+        } else if l.peek::<kw::nofunc>() {
+            parser.parse::<kw::nofunc>()?;
+            Ok(HeapType::NoFunc)
+        } else if l.peek::<kw::noextern>() {
+            parser.parse::<kw::noextern>()?;
+            Ok(HeapType::NoExtern)
+        } else if l.peek::<kw::exn>() {
+            parser.parse::<kw::exn>()?;
+            Ok(HeapType::Exn)
+        } else if l.peek::<kw::noexn>() {
+            parser.parse::<kw::noexn>()?;
+            Ok(HeapType::NoExn)
+        } else if l.peek::<ast::Index>() {
+            Ok(HeapType::Concrete(parser.parse()?))
         } else {
-            Ok(GlobalType {
-                ty: parser.parse()?,
-                mutable: false,
-            })
+            Err(l.error())
         }
     }
 }
 
-/// List of different kinds of table types we can have.
-///
-/// Currently there's only one, a `funcref`.
-#[derive(Copy, Clone, Debug)]
-pub enum TableElemType {
-    /// An element for a table that is a list of functions.
-    Funcref,
-    /// An element for a table that is a list of `anyref` values.
-    Anyref,
-    /// An element for a table that is a list of `nullref` values.
-    Nullref,
+impl Peek for HeapType<'_> {
+    fn peek(cursor: Cursor<'_>) -> bool {
+        kw::func::peek(cursor)
+            || kw::extern_::peek(cursor)
+            || kw::any::peek(cursor)
+            || kw::eq::peek(cursor)
+            || kw::i31::peek(cursor)
+            || kw::none::peek(cursor)
+            || kw::nofunc::peek(cursor)
+            || kw::noextern::peek(cursor)
+            || kw::exn::peek(cursor)
+            || kw::noexn::peek(cursor)
+            || ast::Index::peek(cursor)
+    }
+    fn display() -> &'static str {
+        "heap type"
+    }
+}
+
+/// A reference type, i.e. a nullable or non-nullable reference to some
+/// `HeapType`.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub struct RefType<'a> {
+    /// Whether or not this reference type is nullable, i.e. whether or not it
+    /// can hold a null reference.
+    pub nullable: bool,
+    /// The heap type that this reference type points to.
+    pub heap: HeapType<'a>,
 }
 
-impl<'a> Parse<'a> for TableElemType {
+impl<'a> RefType<'a> {
+    /// A nullable untyped function reference, i.e. `funcref`.
+    pub fn func() -> Self {
+        RefType {
+            nullable: true,
+            heap: HeapType::Func,
+        }
+    }
+
+    /// A nullable external reference, i.e. `externref`.
+    pub fn extern_() -> Self {
+        RefType {
+            nullable: true,
+            heap: HeapType::Extern,
+        }
+    }
+}
+
+impl<'a> Parse<'a> for RefType<'a> {
     fn parse(parser: Parser<'a>) -> Result<Self> {
+        if parser.peek::<kw::funcref>() {
+            parser.parse::<kw::funcref>()?;
+            return Ok(RefType::func());
+        }
         // legacy support for `anyfunc`
         if parser.peek::<kw::anyfunc>() {
             parser.parse::<kw::anyfunc>()?;
-            return Ok(TableElemType::Funcref);
+            return Ok(RefType::func());
         }
-        let mut l = parser.lookahead1();
-        if l.peek::<kw::funcref>() {
-            parser.parse::<kw::funcref>()?;
-            Ok(TableElemType::Funcref)
-        } else if l.peek::<kw::anyref>() {
+        if parser.peek::<kw::externref>() {
+            parser.parse::<kw::externref>()?;
+            return Ok(RefType::extern_());
+        }
+        if parser.peek::<kw::anyref>() {
             parser.parse::<kw::anyref>()?;
-            Ok(TableElemType::Anyref)
-        } else if l.peek::<kw::nullref>() {
+            return Ok(RefType {
+                nullable: true,
+                heap: HeapType::Any,
+            });
+        }
+        if parser.peek::<kw::eqref>() {
+            parser.parse::<kw::eqref>()?;
+            return Ok(RefType {
+                nullable: true,
+                heap: HeapType::Eq,
+            });
+        }
+        if parser.peek::<kw::i31ref>() {
+            parser.parse::<kw::i31ref>()?;
+            return Ok(RefType {
+                nullable: true,
+                heap: HeapType::I31,
+            });
+        }
+        if parser.peek::<kw::nullref>() {
             parser.parse::<kw::nullref>()?;
-            Ok(TableElemType::Nullref)
-        } else {
-            Err(l.error())
+            return Ok(RefType {
+                nullable: true,
+                heap: HeapType::None,
+            });
+        }
+        if parser.peek::<kw::exnref>() {
+            parser.parse::<kw::exnref>()?;
+            return Ok(RefType {
+                nullable: true,
+                heap: HeapType::Exn,
+            });
+        }
+        if parser.peek::<kw::nullexnref>() {
+            parser.parse::<kw::nullexnref>()?;
+            return Ok(RefType {
+                nullable: true,
+                heap: HeapType::NoExn,
+            });
+        }
+        if parser.peek::<kw::nullfuncref>() {
+            parser.parse::<kw::nullfuncref>()?;
+            return Ok(RefType {
+                nullable: true,
+                heap: HeapType::NoFunc,
+            });
         }
+        if parser.peek::<kw::nullexternref>() {
+            parser.parse::<kw::nullexternref>()?;
+            return Ok(RefType {
+                nullable: true,
+                heap: HeapType::NoExtern,
+            });
+        }
+
+        parser.parens(|parser| {
+            parser.parse::<kw::r#ref>()?;
+            let nullable = parser.parse::<Option<kw::null>>()?.is_some();
+            let heap = parser.parse()?;
+            Ok(RefType { nullable, heap })
+        })
     }
 }
 
-impl Peek for TableElemType {
+impl Peek for RefType<'_> {
     fn peek(cursor: Cursor<'_>) -> bool {
         kw::funcref::peek(cursor)
+            || kw::anyfunc::peek(cursor)
+            || kw::externref::peek(cursor)
             || kw::anyref::peek(cursor)
-            || /* legacy */ kw::anyfunc::peek(cursor)
+            || kw::eqref::peek(cursor)
+            || kw::i31ref::peek(cursor)
+            || kw::nullref::peek(cursor)
+            || kw::nullfuncref::peek(cursor)
+            || kw::nullexternref::peek(cursor)
+            || kw::exnref::peek(cursor)
+            || kw::nullexnref::peek(cursor)
+            || (cursor.lparen().is_some() && kw::r#ref::peek2(cursor))
     }
     fn display() -> &'static str {
-        "table element type"
+        "reference type"
+    }
+}
+
+/// Type for a `global` in a wasm module
+#[derive(Copy, Clone, Debug)]
+pub struct GlobalType<'a> {
+    /// The element type of this `global`
+    pub ty: ValType<'a>,
+    /// Whether or not the global is mutable or not.
+    pub mutable: bool,
+}
+
+impl<'a> Parse<'a> for GlobalType<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        if parser.peek2::<kw::r#mut>() {
+            parser.parens(|p| {
+                p.parse::<kw::r#mut>()?;
+                Ok(GlobalType {
+                    ty: parser.parse()?,
+                    mutable: true,
+                })
+            })
+        } else {
+            Ok(GlobalType {
+                ty: parser.parse()?,
+                mutable: false,
+            })
+        }
     }
 }
 
 /// Min/max limits used for tables/memories.
 #[derive(Copy, Clone, Debug)]
 pub struct Limits {
+    /// Whether this is a 64-bit (memory64/table64) or 32-bit limits, as
+    /// indicated by an `i64`/`i32` index-type keyword preceding the bounds.
+    pub is64: bool,
     /// The minimum number of units for this type.
-    pub min: u32,
+    pub min: u64,
     /// An optional maximum number of units for this type.
-    pub max: Option<u32>,
+    pub max: Option<u64>,
 }
 
 impl<'a> Parse<'a> for Limits {
     fn parse(parser: Parser<'a>) -> Result<Self> {
+        let is64 = if parser.peek::<kw::i64>() {
+            parser.parse::<kw::i64>()?;
+            true
+        } else {
+            parser.parse::<Option<kw::i32>>()?;
+            false
+        };
         let min = parser.parse()?;
-        let max = if parser.peek::<u32>() {
+        let max = if parser.peek::<u64>() {
             Some(parser.parse()?)
         } else {
             None
         };
-        Ok(Limits { min, max })
+        Ok(Limits { is64, min, max })
     }
 }
 
 /// Configuration for a table of a wasm mdoule
+///
+/// The `table64` proposal allows `limits` to be 64-bit (see
+/// `Limits::is64`); a 64-bit table's `min`/`max` must still fit in `u32`
+/// until the runtime actually supports table sizes beyond that. If `shared`
+/// is set, validation must also check that `elem`'s heap type is itself
+/// shareable.
 #[derive(Copy, Clone, Debug)]
-pub struct TableType {
+pub struct TableType<'a> {
     /// Limits on the element sizes of this table
     pub limits: Limits,
+    /// Whether or not this is a shared table, usable by multiple agents
+    /// simultaneously
+    pub shared: bool,
     /// The type of element stored in this table
-    pub elem: TableElemType,
+    pub elem: RefType<'a>,
 }
 
-impl<'a> Parse<'a> for TableType {
+impl<'a> Parse<'a> for TableType<'a> {
     fn parse(parser: Parser<'a>) -> Result<Self> {
+        let limits = parser.parse()?;
+        let shared = parser.parse::<Option<kw::shared>>()?.is_some();
+        let elem = parser.parse()?;
         Ok(TableType {
-            limits: parser.parse()?,
-            elem: parser.parse()?,
+            limits,
+            shared,
+            elem,
         })
     }
 }
 
 /// Configuration for a memory of a wasm module
+///
+/// The `memory64` proposal allows `limits` to be 64-bit (see
+/// `Limits::is64`); validation must reject a `max` smaller than `min` and,
+/// for 32-bit memories, any bound larger than `u32::MAX`.
 #[derive(Copy, Clone, Debug)]
 pub struct MemoryType {
     /// Limits on the page sizes of this memory
@@ -187,9 +380,9 @@ impl<'a> Parse<'a> for MemoryType {
 pub struct FunctionType<'a> {
     /// The parameters of a function, optionally each having an identifier for
     /// name resolution and a name for the custom `name` section.
-    pub params: Vec<(Option<ast::Id<'a>>, Option<ast::NameAnnotation<'a>>, ValType)>,
+    pub params: Vec<(Option<ast::Id<'a>>, Option<ast::NameAnnotation<'a>>, ValType<'a>)>,
     /// The results types of a function.
-    pub results: Vec<ValType>,
+    pub results: Vec<ValType<'a>>,
 }
 
 impl<'a> FunctionType<'a> {
@@ -246,22 +439,185 @@ impl<'a> Parse<'a> for FunctionType<'a> {
     }
 }
 
+/// The storage type of a `struct` or `array` field, which may be a full
+/// `ValType` or one of the GC proposal's packed integer types.
+#[allow(missing_docs)]
+#[derive(Debug, Clone)]
+pub enum StorageType<'a> {
+    Val(ValType<'a>),
+    I8,
+    I16,
+}
+
+impl<'a> Parse<'a> for StorageType<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        if parser.peek::<kw::i8>() {
+            parser.parse::<kw::i8>()?;
+            Ok(StorageType::I8)
+        } else if parser.peek::<kw::i16>() {
+            parser.parse::<kw::i16>()?;
+            Ok(StorageType::I16)
+        } else {
+            Ok(StorageType::Val(parser.parse()?))
+        }
+    }
+}
+
+/// A single field of a `struct` (or the lone element of an `array`).
+#[derive(Debug)]
+pub struct StructField<'a> {
+    /// An optional identifier to refer to this field by as part of name
+    /// resolution.
+    pub id: Option<ast::Id<'a>>,
+    /// Whether this field can be written to via `struct.set`/`array.set`.
+    pub mutable: bool,
+    /// The storage type of this field.
+    pub ty: StorageType<'a>,
+}
+
+impl<'a> Parse<'a> for StructField<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        let id = parser.parse()?;
+        let (mutable, ty) = if parser.peek2::<kw::r#mut>() {
+            parser.parens(|p| {
+                p.parse::<kw::r#mut>()?;
+                Ok((true, p.parse()?))
+            })?
+        } else {
+            (false, parser.parse()?)
+        };
+        Ok(StructField { id, mutable, ty })
+    }
+}
+
+/// A GC `struct` type, a product of zero or more named, individually mutable
+/// fields.
+#[derive(Debug)]
+pub struct StructType<'a> {
+    /// The fields of this struct, in declaration order.
+    pub fields: Vec<StructField<'a>>,
+}
+
+impl<'a> Parse<'a> for StructType<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        parser.parse::<kw::r#struct>()?;
+        let mut fields = Vec::new();
+        while parser.peek2::<kw::field>() {
+            fields.push(parser.parens(|p| {
+                p.parse::<kw::field>()?;
+                StructField::parse(p)
+            })?);
+        }
+        Ok(StructType { fields })
+    }
+}
+
+/// A GC `array` type, a homogeneous, variable-length, mutable-or-not
+/// collection of a single storage type.
+#[derive(Debug)]
+pub struct ArrayType<'a> {
+    /// The element of this array, reusing `StructField` since an array is
+    /// simply a struct with a single, unnamed field.
+    pub field: StructField<'a>,
+}
+
+impl<'a> Parse<'a> for ArrayType<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        parser.parse::<kw::array>()?;
+        Ok(ArrayType {
+            field: StructField::parse(parser)?,
+        })
+    }
+}
+
+/// The definition of a `type` declaration: either a classic function type or
+/// one of the GC proposal's aggregate types.
+#[derive(Debug)]
+pub enum TypeDef<'a> {
+    /// A function type, used by `func`/`call_indirect`/etc.
+    Func(FunctionType<'a>),
+    /// A struct type, a product of named, mutable-or-not fields.
+    Struct(StructType<'a>),
+    /// An array type, a homogeneous mutable-or-not collection.
+    Array(ArrayType<'a>),
+}
+
+impl<'a> Parse<'a> for TypeDef<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        let mut l = parser.lookahead1();
+        if l.peek::<kw::func>() {
+            Ok(TypeDef::Func(parser.parse()?))
+        } else if l.peek::<kw::r#struct>() {
+            Ok(TypeDef::Struct(parser.parse()?))
+        } else if l.peek::<kw::array>() {
+            Ok(TypeDef::Array(parser.parse()?))
+        } else {
+            Err(l.error())
+        }
+    }
+}
+
 /// A type declaration in a module
 #[derive(Debug)]
 pub struct Type<'a> {
     /// An optional identifer to refer to this `type` by as part of name
     /// resolution.
     pub id: Option<ast::Id<'a>>,
+    /// The explicit supertypes listed in a `(sub ...)` wrapper, empty if this
+    /// type wasn't declared with one.
+    pub supertypes: Vec<ast::Index<'a>>,
+    /// Whether this type was declared `final`, meaning no other type may
+    /// declare it as a supertype. Types without a `(sub ...)` wrapper are
+    /// implicitly final.
+    pub final_type: bool,
     /// The type that we're declaring.
-    pub func: FunctionType<'a>,
+    pub def: TypeDef<'a>,
 }
 
 impl<'a> Parse<'a> for Type<'a> {
     fn parse(parser: Parser<'a>) -> Result<Self> {
         parser.parse::<kw::r#type>()?;
         let id = parser.parse()?;
-        let func = parser.parens(FunctionType::parse)?;
-        Ok(Type { id, func })
+        let (supertypes, final_type, def) = parser.parens(|p| {
+            if p.peek::<kw::sub>() {
+                p.parse::<kw::sub>()?;
+                let final_type = p.parse::<Option<kw::r#final>>()?.is_some();
+                let mut supertypes = Vec::new();
+                while p.peek::<ast::Index>() {
+                    supertypes.push(p.parse()?);
+                }
+                let def = p.parens(TypeDef::parse)?;
+                Ok((supertypes, final_type, def))
+            } else {
+                Ok((Vec::new(), true, TypeDef::parse(p)?))
+            }
+        })?;
+        Ok(Type {
+            id,
+            supertypes,
+            final_type,
+            def,
+        })
+    }
+}
+
+/// A recursion group, `(rec (type ...) (type ...) ...)`, whose member types
+/// are all simultaneously in scope for name resolution so they may refer to
+/// one another regardless of declaration order.
+#[derive(Debug)]
+pub struct Rec<'a> {
+    /// The types defined as part of this recursion group.
+    pub types: Vec<Type<'a>>,
+}
+
+impl<'a> Parse<'a> for Rec<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        parser.parse::<kw::rec>()?;
+        let mut types = Vec::new();
+        while parser.peek2::<kw::r#type>() {
+            types.push(parser.parens(Type::parse)?);
+        }
+        Ok(Rec { types })
     }
 }
 
@@ -321,3 +677,53 @@ impl<'a> Parse<'a> for TypeUse<'a> {
         TypeUse::parse_allow_names(parser, true)
     }
 }
+
+/// The kind of a `tag`, reserved for future tag kinds beyond exceptions.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+    Exception,
+}
+
+impl<'a> Parse<'a> for Attribute {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        parser.parse::<kw::exception>()?;
+        Ok(Attribute::Exception)
+    }
+}
+
+impl Peek for Attribute {
+    fn peek(cursor: Cursor<'_>) -> bool {
+        kw::exception::peek(cursor)
+    }
+    fn display() -> &'static str {
+        "tag attribute"
+    }
+}
+
+/// A tag type declaration, part of the exception-handling proposal, e.g.
+/// `(tag $e (param i32))`.
+///
+/// Much like a `TypeUse`, a tag can reference an existing function type by
+/// index or declare one inline; either way that function type must have no
+/// results, since a tag only describes the values carried by an exception.
+#[derive(Clone, Debug)]
+pub struct TagType<'a> {
+    /// The attribute of this tag.
+    pub attribute: Attribute,
+    /// The function type describing this tag's parameters.
+    pub ty: TypeUse<'a>,
+}
+
+impl<'a> Parse<'a> for TagType<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        let attribute = parser
+            .parse::<Option<Attribute>>()?
+            .unwrap_or(Attribute::Exception);
+        let ty = parser.parse::<TypeUse<'a>>()?;
+        if ty.ty.results.len() > 0 {
+            return Err(parser.error("tag type must not have any results"));
+        }
+        Ok(TagType { attribute, ty })
+    }
+}
@@ -0,0 +1,7 @@
+//! Support for parsing the text format of the WebAssembly component model.
+//!
+//! This lives alongside, but separate from, `crate::ast` so that the core
+//! wasm type grammar (`ValType`, `Type`, etc.) is unaffected by the
+//! component model's own parallel type grammar.
+
+pub mod types;
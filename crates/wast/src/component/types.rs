@@ -0,0 +1,370 @@
+use crate::ast::{self, kw};
+use crate::parser::{Cursor, Parse, Parser, Peek, Result};
+
+/// One of the component model's built-in primitive value types.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrimitiveValType {
+    Bool,
+    S8,
+    U8,
+    S16,
+    U16,
+    S32,
+    U32,
+    S64,
+    U64,
+    Float32,
+    Float64,
+    Char,
+    String,
+}
+
+impl<'a> Parse<'a> for PrimitiveValType {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        let mut l = parser.lookahead1();
+        if l.peek::<kw::bool_>() {
+            parser.parse::<kw::bool_>()?;
+            Ok(PrimitiveValType::Bool)
+        } else if l.peek::<kw::s8>() {
+            parser.parse::<kw::s8>()?;
+            Ok(PrimitiveValType::S8)
+        } else if l.peek::<kw::u8>() {
+            parser.parse::<kw::u8>()?;
+            Ok(PrimitiveValType::U8)
+        } else if l.peek::<kw::s16>() {
+            parser.parse::<kw::s16>()?;
+            Ok(PrimitiveValType::S16)
+        } else if l.peek::<kw::u16>() {
+            parser.parse::<kw::u16>()?;
+            Ok(PrimitiveValType::U16)
+        } else if l.peek::<kw::s32>() {
+            parser.parse::<kw::s32>()?;
+            Ok(PrimitiveValType::S32)
+        } else if l.peek::<kw::u32>() {
+            parser.parse::<kw::u32>()?;
+            Ok(PrimitiveValType::U32)
+        } else if l.peek::<kw::s64>() {
+            parser.parse::<kw::s64>()?;
+            Ok(PrimitiveValType::S64)
+        } else if l.peek::<kw::u64>() {
+            parser.parse::<kw::u64>()?;
+            Ok(PrimitiveValType::U64)
+        } else if l.peek::<kw::float32>() {
+            parser.parse::<kw::float32>()?;
+            Ok(PrimitiveValType::Float32)
+        } else if l.peek::<kw::float64>() {
+            parser.parse::<kw::float64>()?;
+            Ok(PrimitiveValType::Float64)
+        } else if l.peek::<kw::char_>() {
+            parser.parse::<kw::char_>()?;
+            Ok(PrimitiveValType::Char)
+        } else if l.peek::<kw::string>() {
+            parser.parse::<kw::string>()?;
+            Ok(PrimitiveValType::String)
+        } else {
+            Err(l.error())
+        }
+    }
+}
+
+impl Peek for PrimitiveValType {
+    fn peek(cursor: Cursor<'_>) -> bool {
+        kw::bool_::peek(cursor)
+            || kw::s8::peek(cursor)
+            || kw::u8::peek(cursor)
+            || kw::s16::peek(cursor)
+            || kw::u16::peek(cursor)
+            || kw::s32::peek(cursor)
+            || kw::u32::peek(cursor)
+            || kw::s64::peek(cursor)
+            || kw::u64::peek(cursor)
+            || kw::float32::peek(cursor)
+            || kw::float64::peek(cursor)
+            || kw::char_::peek(cursor)
+            || kw::string::peek(cursor)
+    }
+    fn display() -> &'static str {
+        "primitive value type"
+    }
+}
+
+/// A value type in the component model's type system.
+///
+/// This is the component-model analogue of `ast::ValType`: either one of
+/// the built-in primitives or a reference, by index, to a
+/// `ComponentDefinedType` declared elsewhere.
+#[derive(Debug, Clone)]
+pub enum ComponentValType<'a> {
+    /// A built-in primitive value type.
+    Primitive(PrimitiveValType),
+    /// A reference to a defined type.
+    Type(ast::Index<'a>),
+}
+
+impl<'a> Parse<'a> for ComponentValType<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        if PrimitiveValType::peek(parser.cursor()) {
+            Ok(ComponentValType::Primitive(parser.parse()?))
+        } else {
+            Ok(ComponentValType::Type(parser.parse()?))
+        }
+    }
+}
+
+impl Peek for ComponentValType<'_> {
+    fn peek(cursor: Cursor<'_>) -> bool {
+        PrimitiveValType::peek(cursor) || ast::Index::peek(cursor)
+    }
+    fn display() -> &'static str {
+        "component value type"
+    }
+}
+
+/// A single named field of a `record`, or a named, optionally-payload-bearing
+/// case of a `variant`.
+#[derive(Debug, Clone)]
+pub struct ComponentField<'a> {
+    /// The name of this field or case.
+    pub id: ast::Id<'a>,
+    /// The payload type of this field, or of this case if it carries a
+    /// value.
+    pub ty: Option<ComponentValType<'a>>,
+}
+
+/// A type defined in terms of other component value types: the component
+/// model's analogue of GC's `struct`/`array`, but for a much richer set of
+/// shapes (records, variants, collections, and enums).
+#[derive(Debug, Clone)]
+pub enum ComponentDefinedType<'a> {
+    /// A `record`, a product of named, always-present fields.
+    Record(Vec<ComponentField<'a>>),
+    /// A `variant`, a sum of named cases, each with an optional payload.
+    Variant(Vec<ComponentField<'a>>),
+    /// A `list`, a homogeneous, variable-length collection.
+    List(Box<ComponentValType<'a>>),
+    /// A `tuple`, a fixed-size, heterogeneous product of anonymous fields.
+    Tuple(Vec<ComponentValType<'a>>),
+    /// A set of named `flags`, each independently on or off.
+    Flags(Vec<ast::Id<'a>>),
+    /// An `enum`, a variant whose cases never carry a payload.
+    Enum(Vec<ast::Id<'a>>),
+    /// An `option`, a value that may or may not be present.
+    Option(Box<ComponentValType<'a>>),
+    /// A `result`, either a success value, an error value, or neither.
+    Result {
+        /// The type produced on success, if any.
+        ok: Option<Box<ComponentValType<'a>>>,
+        /// The type produced on failure, if any.
+        err: Option<Box<ComponentValType<'a>>>,
+    },
+}
+
+impl<'a> Parse<'a> for ComponentDefinedType<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        let mut l = parser.lookahead1();
+        if l.peek::<kw::record>() {
+            parser.parse::<kw::record>()?;
+            let mut fields = Vec::new();
+            while parser.peek2::<kw::field>() {
+                fields.push(parser.parens(|p| {
+                    p.parse::<kw::field>()?;
+                    Ok(ComponentField {
+                        id: p.parse()?,
+                        ty: Some(p.parse()?),
+                    })
+                })?);
+            }
+            Ok(ComponentDefinedType::Record(fields))
+        } else if l.peek::<kw::variant>() {
+            parser.parse::<kw::variant>()?;
+            let mut cases = Vec::new();
+            while parser.peek2::<kw::case>() {
+                cases.push(parser.parens(|p| {
+                    p.parse::<kw::case>()?;
+                    let id = p.parse()?;
+                    let ty = if p.is_empty() { None } else { Some(p.parse()?) };
+                    Ok(ComponentField { id, ty })
+                })?);
+            }
+            Ok(ComponentDefinedType::Variant(cases))
+        } else if l.peek::<kw::list>() {
+            parser.parse::<kw::list>()?;
+            Ok(ComponentDefinedType::List(Box::new(parser.parse()?)))
+        } else if l.peek::<kw::tuple>() {
+            parser.parse::<kw::tuple>()?;
+            let mut types = Vec::new();
+            while !parser.is_empty() {
+                types.push(parser.parse()?);
+            }
+            Ok(ComponentDefinedType::Tuple(types))
+        } else if l.peek::<kw::flags>() {
+            parser.parse::<kw::flags>()?;
+            let mut names = Vec::new();
+            while !parser.is_empty() {
+                names.push(parser.parse()?);
+            }
+            Ok(ComponentDefinedType::Flags(names))
+        } else if l.peek::<kw::r#enum>() {
+            parser.parse::<kw::r#enum>()?;
+            let mut names = Vec::new();
+            while !parser.is_empty() {
+                names.push(parser.parse()?);
+            }
+            Ok(ComponentDefinedType::Enum(names))
+        } else if l.peek::<kw::option>() {
+            parser.parse::<kw::option>()?;
+            Ok(ComponentDefinedType::Option(Box::new(parser.parse()?)))
+        } else if l.peek::<kw::result>() {
+            parser.parse::<kw::result>()?;
+            let ok = if parser.peek2::<kw::ok>() {
+                Some(Box::new(parser.parens(|p| {
+                    p.parse::<kw::ok>()?;
+                    p.parse()
+                })?))
+            } else {
+                None
+            };
+            let err = if parser.peek2::<kw::error>() {
+                Some(Box::new(parser.parens(|p| {
+                    p.parse::<kw::error>()?;
+                    p.parse()
+                })?))
+            } else {
+                None
+            };
+            Ok(ComponentDefinedType::Result { ok, err })
+        } else {
+            Err(l.error())
+        }
+    }
+}
+
+/// A single named, typed parameter of a `ComponentFunctionType`.
+///
+/// Unlike core wasm function types, every parameter here is named; the
+/// function's single result is an unnamed `ComponentValType`.
+#[derive(Debug, Clone)]
+pub struct ComponentFunctionParam<'a> {
+    /// The name of this parameter.
+    pub id: ast::Id<'a>,
+    /// The type of this parameter.
+    pub ty: ComponentValType<'a>,
+}
+
+impl<'a> Parse<'a> for ComponentFunctionParam<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        Ok(ComponentFunctionParam {
+            id: parser.parse()?,
+            ty: parser.parse()?,
+        })
+    }
+}
+
+/// A component-level function type, e.g. `(func (param $x string) (result
+/// bool))`.
+///
+/// Components permit only a single result type, unlike core wasm's
+/// multi-value `FunctionType`.
+#[derive(Debug, Clone)]
+pub struct ComponentFunctionType<'a> {
+    /// The named, typed parameters of this function.
+    pub params: Vec<ComponentFunctionParam<'a>>,
+    /// The single result type of this function, if any.
+    pub result: Option<ComponentValType<'a>>,
+}
+
+impl<'a> Parse<'a> for ComponentFunctionType<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        parser.parse::<kw::func>()?;
+        let mut params = Vec::new();
+        while parser.peek2::<kw::param>() {
+            parser.parens(|p| {
+                p.parse::<kw::param>()?;
+                params.push(ComponentFunctionParam::parse(p)?);
+                Ok(())
+            })?;
+        }
+        let result = if parser.peek2::<kw::result>() {
+            Some(parser.parens(|p| {
+                p.parse::<kw::result>()?;
+                p.parse()
+            })?)
+        } else {
+            None
+        };
+        Ok(ComponentFunctionType { params, result })
+    }
+}
+
+/// A single item exported (or imported) by a `ComponentType`/`InstanceType`,
+/// named and described by one of the type subsystem's definitions.
+#[derive(Debug, Clone)]
+pub struct ComponentTypeItem<'a> {
+    /// The name this item is exported or imported under.
+    pub name: ast::Id<'a>,
+    /// A reference to the type describing this item.
+    pub ty: ComponentValType<'a>,
+}
+
+impl<'a> Parse<'a> for ComponentTypeItem<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        Ok(ComponentTypeItem {
+            name: parser.parse()?,
+            ty: parser.parse()?,
+        })
+    }
+}
+
+/// An `instance` type: the type of a component instance, described purely in
+/// terms of what it exports.
+#[derive(Debug, Clone)]
+pub struct InstanceType<'a> {
+    /// The named items this instance exports.
+    pub exports: Vec<ComponentTypeItem<'a>>,
+}
+
+impl<'a> Parse<'a> for InstanceType<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        parser.parse::<kw::instance>()?;
+        let mut exports = Vec::new();
+        while parser.peek2::<kw::export>() {
+            exports.push(parser.parens(|p| {
+                p.parse::<kw::export>()?;
+                ComponentTypeItem::parse(p)
+            })?);
+        }
+        Ok(InstanceType { exports })
+    }
+}
+
+/// A `component` type: the type of a whole component, described in terms of
+/// what it imports and what it exports.
+#[derive(Debug, Clone)]
+pub struct ComponentType<'a> {
+    /// The named items this component imports.
+    pub imports: Vec<ComponentTypeItem<'a>>,
+    /// The named items this component exports.
+    pub exports: Vec<ComponentTypeItem<'a>>,
+}
+
+impl<'a> Parse<'a> for ComponentType<'a> {
+    fn parse(parser: Parser<'a>) -> Result<Self> {
+        parser.parse::<kw::component>()?;
+        let mut imports = Vec::new();
+        while parser.peek2::<kw::import>() {
+            imports.push(parser.parens(|p| {
+                p.parse::<kw::import>()?;
+                ComponentTypeItem::parse(p)
+            })?);
+        }
+        let mut exports = Vec::new();
+        while parser.peek2::<kw::export>() {
+            exports.push(parser.parens(|p| {
+                p.parse::<kw::export>()?;
+                ComponentTypeItem::parse(p)
+            })?);
+        }
+        Ok(ComponentType { imports, exports })
+    }
+}